@@ -1,44 +1,114 @@
+use std::sync::OnceLock;
+
 use burn::tensor::{backend::Backend, Data, Tensor};
-use rustfft::{num_complex::Complex32, FftPlanner};
+use realfft::{num_complex::Complex32, RealFftPlanner, RealToComplex};
 
 pub const SAMPLE_RATE: usize = 16000;
 pub const N_FFT: usize = 400;
 pub const HOP_LENGTH: usize = 160;
 pub const N_MELS: usize = 80;
+const N_FREQS: usize = N_FFT / 2 + 1;
+
+/// Precomputed, reusable pieces of the mel spectrogram pipeline: the real FFT
+/// plan, the analysis window, and the mel filterbank. Building these is far
+/// more expensive than running them, so they're planned once and shared
+/// across every frame and every call to [`prep_audio`].
+struct MelSpectrogramContext {
+    fft: std::sync::Arc<dyn RealToComplex<f32>>,
+    window: [f32; N_FFT],
+    mel_filters: Vec<[f32; N_FREQS]>,
+}
+
+fn context() -> &'static MelSpectrogramContext {
+    static CONTEXT: OnceLock<MelSpectrogramContext> = OnceLock::new();
+    CONTEXT.get_or_init(|| {
+        let mut planner = RealFftPlanner::<f32>::new();
+        MelSpectrogramContext {
+            fft: planner.plan_fft_forward(N_FFT),
+            window: hann_window(),
+            mel_filters: mel_filterbank(N_MELS, SAMPLE_RATE as f64),
+        }
+    })
+}
+
+/// Averages interleaved multi-channel samples down to a single mono channel.
+/// A single-channel input is returned unchanged. A trailing short frame
+/// (`samples.len()` not a multiple of `channels`) is averaged over the
+/// channels actually present in it, not the full `channels` count.
+pub fn downmix_to_mono(samples: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return samples.to_vec();
+    }
+
+    samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+/// Resamples `samples` from `from_rate` to `to_rate` via linear interpolation.
+/// Produces `floor(samples.len() * to_rate / from_rate)` output samples.
+pub fn resample_linear(samples: &[f32], from_rate: usize, to_rate: usize) -> Vec<f32> {
+    if from_rate == to_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let new_len = samples.len() * to_rate / from_rate;
+    let ratio = from_rate as f64 / to_rate as f64;
+
+    (0..new_len)
+        .map(|i| {
+            let src_pos = i as f64 * ratio;
+            let src_idx = src_pos.floor() as usize;
+            let frac = (src_pos - src_idx as f64) as f32;
+
+            let a = samples[src_idx.min(samples.len() - 1)];
+            let b = samples[(src_idx + 1).min(samples.len() - 1)];
+
+            a + (b - a) * frac
+        })
+        .collect()
+}
 
 /// Converts a 16 kHz mono waveform into the log-mel spectrogram Whisper's
 /// encoder expects, shaped `[1, N_MELS, n_frames]`.
 pub fn prep_audio<B: Backend>(waveform: &[f32], sample_rate: f64) -> Tensor<B, 3> {
     assert_eq!(sample_rate as usize, SAMPLE_RATE, "prep_audio expects 16kHz audio");
 
-    let window = hann_window(N_FFT);
-    let mel_filters = mel_filterbank(N_MELS, N_FFT, SAMPLE_RATE as f64);
+    let (mel_spec, n_frames) = log_mel_spectrogram(waveform);
+
+    Tensor::from_data(Data::new(mel_spec, [1, N_MELS, n_frames].into()), &Default::default())
+}
 
-    let mut planner = FftPlanner::<f32>::new();
-    let fft = planner.plan_fft_forward(N_FFT);
+/// The backend-independent bulk of [`prep_audio`]: windowed real FFT, mel
+/// filterbank, then the standard Whisper log/clamp. Split out from
+/// `prep_audio` so it can be unit-tested without a `Tensor`/`Backend`.
+fn log_mel_spectrogram(waveform: &[f32]) -> (Vec<f32>, usize) {
+    let ctx = context();
 
-    let n_frames = 1 + (waveform.len().saturating_sub(N_FFT)) / HOP_LENGTH;
+    let n_frames = 1 + waveform.len().saturating_sub(N_FFT) / HOP_LENGTH;
     let mut mel_spec = vec![0.0f32; N_MELS * n_frames];
 
+    let mut input = ctx.fft.make_input_vec();
+    let mut output = ctx.fft.make_output_vec();
+    let mut scratch = ctx.fft.make_scratch_vec();
+
     for frame_idx in 0..n_frames {
         let start = frame_idx * HOP_LENGTH;
-        let mut buffer: Vec<Complex32> = (0..N_FFT)
-            .map(|i| {
-                let sample = waveform.get(start + i).copied().unwrap_or(0.0);
-                Complex32::new(sample * window[i], 0.0)
-            })
-            .collect();
+        for i in 0..N_FFT {
+            let sample = waveform.get(start + i).copied().unwrap_or(0.0);
+            input[i] = sample * ctx.window[i];
+        }
 
-        fft.process(&mut buffer);
+        ctx.fft
+            .process_with_scratch(&mut input, &mut output, &mut scratch)
+            .expect("real FFT of a fixed-size frame should never fail");
 
-        let power: Vec<f32> = buffer[..N_FFT / 2 + 1]
-            .iter()
-            .map(|c| c.norm_sqr())
-            .collect();
+        let power: Vec<f32> = output.iter().map(Complex32::norm_sqr).collect();
 
         for mel_idx in 0..N_MELS {
             let mut sum = 0.0;
-            for (bin, &weight) in mel_filters[mel_idx].iter().enumerate() {
+            for (bin, &weight) in ctx.mel_filters[mel_idx].iter().enumerate() {
                 sum += weight * power[bin];
             }
             mel_spec[mel_idx * n_frames + frame_idx] = sum;
@@ -52,19 +122,117 @@ pub fn prep_audio<B: Backend>(waveform: &[f32], sample_rate: f64) -> Tensor<B, 3
         *v = (clamped + 4.0) / 4.0;
     }
 
-    Tensor::from_data(Data::new(mel_spec, [1, N_MELS, n_frames].into()), &Default::default())
+    (mel_spec, n_frames)
 }
 
-fn hann_window(len: usize) -> Vec<f32> {
-    (0..len)
-        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / len as f32).cos())
-        .collect()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downmix_passes_mono_through_unchanged() {
+        let samples = vec![0.1, -0.2, 0.3];
+        assert_eq!(downmix_to_mono(&samples, 1), samples);
+    }
+
+    #[test]
+    fn downmix_averages_interleaved_channels() {
+        let samples = vec![1.0, -1.0, 0.5, 0.5];
+        assert_eq!(downmix_to_mono(&samples, 2), vec![0.0, 0.5]);
+    }
+
+    #[test]
+    fn downmix_handles_empty_input() {
+        assert_eq!(downmix_to_mono(&[], 2), Vec::<f32>::new());
+    }
+
+    #[test]
+    fn downmix_averages_a_short_trailing_frame_over_its_own_length() {
+        // 5 samples at 2 channels: one full stereo frame, then a lone
+        // trailing sample that should average over itself, not over 2.
+        let samples = vec![1.0, -1.0, 3.0];
+        assert_eq!(downmix_to_mono(&samples, 2), vec![0.0, 3.0]);
+    }
+
+    #[test]
+    fn resample_passes_through_when_rates_match() {
+        let samples = vec![1.0, 2.0, 3.0];
+        assert_eq!(resample_linear(&samples, 16000, 16000), samples);
+    }
+
+    #[test]
+    fn resample_handles_empty_input() {
+        assert_eq!(resample_linear(&[], 8000, 16000), Vec::<f32>::new());
+    }
+
+    #[test]
+    fn resample_upsamples_to_expected_length() {
+        let samples = vec![0.0, 1.0, 2.0, 3.0];
+        let resampled = resample_linear(&samples, 8000, 16000);
+        assert_eq!(resampled.len(), samples.len() * 16000 / 8000);
+        assert_eq!(resampled[0], 0.0);
+        assert_eq!(resampled[2], 1.0);
+    }
+
+    #[test]
+    fn resample_floors_a_non_integer_length_ratio() {
+        // 3 samples at a 3:2 downsample ratio floors to 2, not 2.something.
+        let samples = vec![0.0, 3.0, 6.0];
+        let resampled = resample_linear(&samples, 3, 2);
+        assert_eq!(resampled.len(), 2);
+    }
+
+    fn sine_wave(freq_hz: f64, n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| (2.0 * std::f64::consts::PI * freq_hz * i as f64 / SAMPLE_RATE as f64).sin() as f32)
+            .collect()
+    }
+
+    #[test]
+    fn silence_produces_the_log_clamp_floor_in_every_mel_bin() {
+        let (mel_spec, n_frames) = log_mel_spectrogram(&vec![0.0f32; N_FFT]);
+        assert_eq!(n_frames, 1);
+        assert!(mel_spec.iter().all(|&v| (v - (-1.5)).abs() < 1e-5), "{:?}", mel_spec);
+    }
+
+    #[test]
+    fn a_low_frequency_tone_peaks_in_a_low_mel_bin() {
+        let (mel_spec, n_frames) = log_mel_spectrogram(&sine_wave(200.0, N_FFT));
+        assert_eq!(n_frames, 1);
+        let peak_bin = (0..N_MELS).max_by(|&a, &b| mel_spec[a].partial_cmp(&mel_spec[b]).unwrap()).unwrap();
+        assert!(peak_bin < N_MELS / 4, "expected a low-frequency tone to peak in a low mel bin, got {}", peak_bin);
+    }
+
+    #[test]
+    fn a_high_frequency_tone_peaks_in_a_high_mel_bin() {
+        let (mel_spec, n_frames) = log_mel_spectrogram(&sine_wave(6000.0, N_FFT));
+        assert_eq!(n_frames, 1);
+        let peak_bin = (0..N_MELS).max_by(|&a, &b| mel_spec[a].partial_cmp(&mel_spec[b]).unwrap()).unwrap();
+        assert!(peak_bin > N_MELS * 3 / 4, "expected a high-frequency tone to peak in a high mel bin, got {}", peak_bin);
+    }
+
+    #[test]
+    fn mel_filterbank_rows_are_normalized_triangles_within_the_frequency_range() {
+        let filters = mel_filterbank(N_MELS, SAMPLE_RATE as f64);
+        assert_eq!(filters.len(), N_MELS);
+        for filter in &filters {
+            assert!(filter.iter().all(|&w| (0.0..=1.0).contains(&w)));
+            assert!(filter.iter().any(|&w| w > 0.0));
+        }
+    }
+}
+
+fn hann_window() -> [f32; N_FFT] {
+    let mut window = [0.0f32; N_FFT];
+    for (i, w) in window.iter_mut().enumerate() {
+        *w = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / N_FFT as f32).cos();
+    }
+    window
 }
 
 /// Builds the `n_mels x (n_fft/2 + 1)` triangular mel filterbank used to
 /// collapse FFT power bins down onto the mel scale.
-fn mel_filterbank(n_mels: usize, n_fft: usize, sample_rate: f64) -> Vec<Vec<f32>> {
-    let n_bins = n_fft / 2 + 1;
+fn mel_filterbank(n_mels: usize, sample_rate: f64) -> Vec<[f32; N_FREQS]> {
     let hz_to_mel = |hz: f64| 2595.0 * (1.0 + hz / 700.0).log10();
     let mel_to_hz = |mel: f64| 700.0 * (10f64.powf(mel / 2595.0) - 1.0);
 
@@ -77,7 +245,7 @@ fn mel_filterbank(n_mels: usize, n_fft: usize, sample_rate: f64) -> Vec<Vec<f32>
     let hz_points: Vec<f64> = mel_points.iter().map(|&m| mel_to_hz(m)).collect();
     let bin_points: Vec<f64> = hz_points
         .iter()
-        .map(|&hz| (n_fft as f64 + 1.0) * hz / sample_rate)
+        .map(|&hz| (N_FFT as f64 + 1.0) * hz / sample_rate)
         .collect();
 
     (0..n_mels)
@@ -86,18 +254,18 @@ fn mel_filterbank(n_mels: usize, n_fft: usize, sample_rate: f64) -> Vec<Vec<f32>
             let center = bin_points[mel_idx + 1];
             let right = bin_points[mel_idx + 2];
 
-            (0..n_bins)
-                .map(|bin| {
-                    let bin = bin as f64;
-                    if bin < left || bin > right {
-                        0.0
-                    } else if bin <= center {
-                        ((bin - left) / (center - left)) as f32
-                    } else {
-                        ((right - bin) / (right - center)) as f32
-                    }
-                })
-                .collect()
+            let mut filter = [0.0f32; N_FREQS];
+            for (bin, weight) in filter.iter_mut().enumerate() {
+                let bin = bin as f64;
+                *weight = if bin < left || bin > right {
+                    0.0
+                } else if bin <= center {
+                    ((bin - left) / (center - left)) as f32
+                } else {
+                    ((right - bin) / (right - center)) as f32
+                };
+            }
+            filter
         })
         .collect()
 }
@@ -0,0 +1,328 @@
+use std::sync::mpsc::{self, Receiver};
+
+use whisper::model::*;
+use whisper::transcribe::{waveform_to_text, DecodeOptions};
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "torch-backend")] {
+        use burn_tch::{TchBackend, TchDevice};
+    } else if #[cfg(feature = "wgpu-backend")] {
+        use burn_wgpu::{WgpuBackend, WgpuDevice, AutoGraphicsApi};
+    }
+}
+
+use burn::{config::Config, module::Module, tensor::backend::Backend};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+use whisper::audio::{downmix_to_mono, resample_linear};
+use whisper::token::Gpt2Tokenizer;
+use burn::record::{Recorder, DefaultRecorder, RecorderError};
+
+use std::{env, process};
+
+const SAMPLE_RATE: usize = 16000;
+const FRAME_LEN: usize = 1600; // 100 ms frames at 16 kHz
+const WINDOW_LEN: usize = SAMPLE_RATE * 30; // transcribe in ~30s windows
+const TRAILING_SILENCE_MS: usize = 700;
+const TRAILING_SILENCE_FRAMES: usize = TRAILING_SILENCE_MS / 100;
+
+/// Cheap voice-activity detector based on short-term energy and zero-crossing
+/// rate. Good enough to gate silence out of a live microphone stream without
+/// pulling in a dedicated VAD model.
+struct EnergyVad {
+    energy_threshold: f32,
+    zcr_threshold: f32,
+}
+
+impl EnergyVad {
+    fn new() -> Self {
+        Self {
+            energy_threshold: 0.01,
+            zcr_threshold: 0.15,
+        }
+    }
+
+    fn is_speech(&self, frame: &[f32]) -> bool {
+        let energy = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+
+        let zero_crossings = frame
+            .windows(2)
+            .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+            .count();
+        let zcr = zero_crossings as f32 / frame.len() as f32;
+
+        energy >= self.energy_threshold && zcr <= self.zcr_threshold
+    }
+}
+
+/// Downmixes an interleaved native-format frame to mono, accumulates it at
+/// the device's native sample rate, and drains/resamples it down to 16 kHz
+/// in `FRAME_LEN`-sized chunks whenever enough native audio has built up.
+fn push_native_frame(
+    data: &[f32],
+    channels: usize,
+    native_sample_rate: usize,
+    native_frame_len: usize,
+    pending_native: &mut Vec<f32>,
+    tx: &mpsc::Sender<Vec<f32>>,
+) {
+    pending_native.extend(downmix_to_mono(data, channels));
+
+    while pending_native.len() >= native_frame_len {
+        let chunk: Vec<f32> = pending_native.drain(..native_frame_len).collect();
+        let mut resampled = resample_linear(&chunk, native_sample_rate, SAMPLE_RATE);
+        resampled.resize(FRAME_LEN, 0.0);
+        let _ = tx.send(resampled);
+    }
+}
+
+/// Opens the default input device and returns a channel of 100 ms f32 mono
+/// frames at 16 kHz, resampling/downmixing on the fly if the device's native
+/// format differs.
+fn open_microphone_stream() -> Result<(cpal::Stream, Receiver<Vec<f32>>), cpal::BuildStreamError> {
+    let host = cpal::default_host();
+    let device = host
+        .default_input_device()
+        .expect("no input device available");
+    let supported_config = device
+        .default_input_config()
+        .expect("no default input config");
+
+    let sample_format = supported_config.sample_format();
+    let config = supported_config.config();
+    let channels = config.channels as usize;
+    let native_sample_rate = config.sample_rate.0 as usize;
+    // Enough native-rate samples to always yield at least one FRAME_LEN chunk
+    // once resampled down to SAMPLE_RATE.
+    let native_frame_len = (FRAME_LEN * native_sample_rate).div_ceil(SAMPLE_RATE);
+
+    let (tx, rx) = mpsc::channel();
+    let mut pending_native = Vec::new();
+    let err_fn = |err| eprintln!("Audio stream error: {}", err);
+
+    let stream = match sample_format {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &config,
+            move |data: &[f32], _| {
+                push_native_frame(data, channels, native_sample_rate, native_frame_len, &mut pending_native, &tx);
+            },
+            err_fn,
+            None,
+        )?,
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &config,
+            move |data: &[i16], _| {
+                let floats: Vec<f32> = data.iter().map(|&s| s as f32 / i16::MAX as f32).collect();
+                push_native_frame(&floats, channels, native_sample_rate, native_frame_len, &mut pending_native, &tx);
+            },
+            err_fn,
+            None,
+        )?,
+        cpal::SampleFormat::U16 => device.build_input_stream(
+            &config,
+            move |data: &[u16], _| {
+                let floats: Vec<f32> = data
+                    .iter()
+                    .map(|&s| (s as f32 / u16::MAX as f32) * 2.0 - 1.0)
+                    .collect();
+                push_native_frame(&floats, channels, native_sample_rate, native_frame_len, &mut pending_native, &tx);
+            },
+            err_fn,
+            None,
+        )?,
+        other => {
+            return Err(cpal::BuildStreamError::BackendSpecific {
+                err: cpal::BackendSpecificError {
+                    description: format!("unsupported input sample format: {:?}", other),
+                },
+            })
+        }
+    };
+
+    Ok((stream, rx))
+}
+
+/// Accumulates gated speech frames and flushes a buffer once trailing silence
+/// or the 30 s window limit is reached.
+struct SpeechBuffer {
+    samples: Vec<f32>,
+    silence_run: usize,
+}
+
+impl SpeechBuffer {
+    fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+            silence_run: 0,
+        }
+    }
+
+    /// Feeds one gated frame in. Returns `Some(buffer)` with the accumulated
+    /// speech once it should be flushed to the model.
+    fn push(&mut self, frame: Vec<f32>, is_speech: bool) -> Option<Vec<f32>> {
+        if is_speech {
+            self.silence_run = 0;
+            self.samples.extend(frame);
+        } else if !self.samples.is_empty() {
+            self.silence_run += 1;
+            self.samples.extend(frame);
+        }
+
+        let should_flush = self.samples.len() >= WINDOW_LEN
+            || (!self.samples.is_empty() && self.silence_run >= TRAILING_SILENCE_FRAMES);
+
+        if should_flush {
+            self.silence_run = 0;
+            Some(std::mem::take(&mut self.samples))
+        } else {
+            None
+        }
+    }
+}
+
+fn load_whisper_model_file<B: Backend>(config: &WhisperConfig, filename: &str) -> Result<Whisper<B>, RecorderError> {
+    DefaultRecorder::new()
+        .load(filename.into())
+        .map(|record| config.init().load_record(record))
+}
+
+fn main() {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "torch-backend")] {
+            type Backend = TchBackend<f32>;
+            let device = TchDevice::Cuda(0);
+        } else if #[cfg(feature = "wgpu-backend")] {
+            type Backend = WgpuBackend<AutoGraphicsApi, f32, i32>;
+            let device = WgpuDevice::BestAvailable;
+        }
+    }
+
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 {
+        eprintln!("Usage: {} <model name>", args[0]);
+        process::exit(1);
+    }
+    let model_name = &args[1];
+
+    let bpe = match Gpt2Tokenizer::new() {
+        Ok(bpe) => bpe,
+        Err(e) => {
+            eprintln!("Failed to load tokenizer: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let whisper_config = match WhisperConfig::load(&format!("{}.cfg", model_name)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load whisper config: {}", e);
+            process::exit(1);
+        }
+    };
+
+    println!("Loading model...");
+    let whisper: Whisper<Backend> = match load_whisper_model_file(&whisper_config, model_name) {
+        Ok(whisper_model) => whisper_model,
+        Err(e) => {
+            eprintln!("Failed to load whisper model file: {}", e);
+            process::exit(1);
+        }
+    };
+    let whisper = whisper.to_device(&device);
+
+    let (_stream_handle, frames) = open_microphone_stream().unwrap_or_else(|e| {
+        eprintln!("Failed to open microphone: {}", e);
+        process::exit(1);
+    });
+    _stream_handle.play().unwrap_or_else(|e| {
+        eprintln!("Failed to start microphone stream: {}", e);
+        process::exit(1);
+    });
+
+    println!("Listening... (Ctrl+C to stop)");
+
+    let vad = EnergyVad::new();
+    let mut buffer = SpeechBuffer::new();
+    let decode_options = DecodeOptions::default();
+
+    for frame in frames {
+        let is_speech = vad.is_speech(&frame);
+
+        if let Some(window) = buffer.push(frame, is_speech) {
+            match waveform_to_text(&whisper, &bpe, window, SAMPLE_RATE, &decode_options) {
+                Ok( (text, _tokens, _segments, _language) ) => {
+                    if !text.trim().is_empty() {
+                        println!("{}", text.trim());
+                    }
+                }
+                Err(e) => eprintln!("Error during transcription: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vad_flags_a_loud_low_zcr_frame_as_speech() {
+        let vad = EnergyVad::new();
+        let frame: Vec<f32> = (0..FRAME_LEN).map(|i| ((i as f32) * 0.05).sin()).collect();
+        assert!(vad.is_speech(&frame));
+    }
+
+    #[test]
+    fn vad_flags_silence_as_not_speech() {
+        let vad = EnergyVad::new();
+        let frame = vec![0.0; FRAME_LEN];
+        assert!(!vad.is_speech(&frame));
+    }
+
+    #[test]
+    fn vad_flags_a_quiet_frame_below_the_energy_threshold_as_not_speech() {
+        let vad = EnergyVad::new();
+        let frame: Vec<f32> = (0..FRAME_LEN).map(|i| ((i as f32) * 0.05).sin() * 0.001).collect();
+        assert!(!vad.is_speech(&frame));
+    }
+
+    #[test]
+    fn speech_buffer_drops_silence_before_any_speech_arrives() {
+        let mut buffer = SpeechBuffer::new();
+        for _ in 0..(TRAILING_SILENCE_FRAMES + 5) {
+            assert_eq!(buffer.push(vec![0.0; FRAME_LEN], false), None);
+        }
+        assert!(buffer.samples.is_empty());
+    }
+
+    #[test]
+    fn speech_buffer_flushes_after_trailing_silence() {
+        let mut buffer = SpeechBuffer::new();
+        assert_eq!(buffer.push(vec![1.0; FRAME_LEN], true), None);
+
+        let mut flushed = None;
+        for _ in 0..TRAILING_SILENCE_FRAMES {
+            flushed = buffer.push(vec![0.0; FRAME_LEN], false);
+        }
+
+        let flushed = flushed.expect("buffer should flush once trailing silence elapses");
+        assert_eq!(flushed.len(), FRAME_LEN * (TRAILING_SILENCE_FRAMES + 1));
+        assert!(buffer.samples.is_empty());
+    }
+
+    #[test]
+    fn speech_buffer_flushes_on_hitting_the_window_limit_even_mid_speech() {
+        let mut buffer = SpeechBuffer::new();
+        let frames_per_window = WINDOW_LEN / FRAME_LEN;
+
+        let mut flushed = None;
+        for _ in 0..frames_per_window {
+            flushed = buffer.push(vec![1.0; FRAME_LEN], true);
+        }
+
+        let flushed = flushed.expect("buffer should flush once WINDOW_LEN samples accumulate");
+        assert_eq!(flushed.len(), FRAME_LEN * frames_per_window);
+    }
+}
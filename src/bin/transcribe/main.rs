@@ -4,7 +4,8 @@ use std::iter;
 use whisper::model::*;
 use whisper::helper::*;
 use whisper::token;
-use whisper::transcribe::waveform_to_text;
+use whisper::transcribe::{waveform_to_text, DecodeOptions, Task};
+use whisper::subtitle::{format_transcription, SubtitleFormat};
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "torch-backend")] {
@@ -29,44 +30,93 @@ use burn::{
 
 use hound::{self, SampleFormat};
 
+const TARGET_SAMPLE_RATE: usize = 16000;
+const DEFAULT_QUANTIZATION_BLOCK_SIZE: usize = 32;
+
+/// Removes a `--flag` or `--flag=value` argument from `args` in place and
+/// returns the parsed block size: `DEFAULT_QUANTIZATION_BLOCK_SIZE` for a
+/// bare flag, the parsed value for `--flag=value`, or `None` if absent.
+fn extract_flag_value(args: &mut Vec<String>, flag: &str) -> Option<usize> {
+    let prefix = format!("{}=", flag);
+    let index = args.iter().position(|a| a == flag || a.starts_with(&prefix))?;
+    let arg = args.remove(index);
+
+    Some(
+        arg.strip_prefix(&prefix)
+            .map(|value| value.parse().expect("expected an integer block size"))
+            .unwrap_or(DEFAULT_QUANTIZATION_BLOCK_SIZE),
+    )
+}
+
+/// Removes a bare `--flag` argument from `args` in place, returning whether
+/// it was present.
+fn take_bool_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|a| a == flag) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Removes a `--flag=value` argument from `args` in place, returning `value`
+/// if present.
+fn take_string_flag(args: &mut Vec<String>, flag: &str) -> Option<String> {
+    let prefix = format!("{}=", flag);
+    let index = args.iter().position(|a| a.starts_with(&prefix))?;
+    let arg = args.remove(index);
+    Some(arg[prefix.len()..].to_string())
+}
+
 fn load_audio_waveform<B: Backend>(filename: &str) -> hound::Result<(Vec<f32>, usize)> {
     let mut reader = hound::WavReader::open(filename)?;
     let spec = reader.spec();
 
-    let duration = reader.duration() as usize;
     let channels = spec.channels as usize;
     let sample_rate = spec.sample_rate as usize;
-    let bits_per_sample = spec.bits_per_sample;
     let sample_format = spec.sample_format;
 
-    assert_eq!(sample_rate, 16000, "The audio sample rate must be 16k.");
-    assert_eq!(channels, 1, "The audio must be single-channel.");
-
     let max_int_val = 2_u32.pow(spec.bits_per_sample as u32 - 1) - 1;
 
-    let floats = match sample_format {
+    let floats: Vec<f32> = match sample_format {
         SampleFormat::Float => reader
             .into_samples::<f32>()
-            .collect::<hound::Result<_>>()?, 
+            .collect::<hound::Result<_>>()?,
         SampleFormat::Int => reader
             .into_samples::<i32>()
             .map(|s| s.map(|s| s as f32 / max_int_val as f32))
-            .collect::<hound::Result<_>>()?, 
+            .collect::<hound::Result<_>>()?,
     };
 
-    return Ok( (floats, sample_rate) );
+    let mono = downmix_to_mono(&floats, channels);
+    let resampled = resample_linear(&mono, sample_rate, TARGET_SAMPLE_RATE);
+
+    return Ok( (resampled, TARGET_SAMPLE_RATE) );
 }
 
 use num_traits::ToPrimitive;
-use whisper::audio::prep_audio;
+use whisper::audio::{downmix_to_mono, prep_audio, resample_linear};
 use whisper::token::{Gpt2Tokenizer, SpecialToken};
 
 use burn::record::{Recorder, DefaultRecorder, RecorderError};
-
-fn load_whisper_model_file<B: Backend>(config: &WhisperConfig, filename: &str) -> Result<Whisper<B>, RecorderError> {
-    DefaultRecorder::new()
-    .load(filename.into())
-    .map(|record| config.init().load_record(record))
+use whisper::quantize::QuantizationConfig;
+
+/// Loads `filename` as a full fp32 checkpoint via `DefaultRecorder`, then
+/// quantizes it in place if `config.quantization` is set. See the
+/// `whisper::quantize` module docs for what `--quantize` does and doesn't
+/// trade off.
+fn load_whisper_model_file<B: Backend>(
+    config: &WhisperConfig,
+    filename: &str,
+) -> Result<Whisper<B>, RecorderError> {
+    DefaultRecorder::new().load(filename.into()).map(|record| {
+        let whisper = config.init().load_record(record);
+        match &config.quantization {
+            Some(quant_config) => whisper.quantize(quant_config),
+            None => whisper,
+        }
+    })
 }
 
 use std::{env, process, fs};
@@ -82,10 +132,22 @@ fn main() {
         }
     }
 
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+    let quantize_block_size = extract_flag_value(&mut args, "--quantize");
+    let language = take_string_flag(&mut args, "--language");
+    let translate = take_bool_flag(&mut args, "--translate");
+    let no_context = take_bool_flag(&mut args, "--no-context");
+    let temperature: f32 = take_string_flag(&mut args, "--temperature")
+        .map(|v| v.parse().expect("expected a float temperature"))
+        .unwrap_or(0.0);
+    let beam_size: Option<usize> = take_string_flag(&mut args, "--beam")
+        .map(|v| v.parse().expect("expected an integer beam size"));
 
     if args.len() < 4 {
-        eprintln!("Usage: {} <model name> <audio file> <transcription file>", args[0]);
+        eprintln!(
+            "Usage: {} <model name> <audio file> <transcription file> [--quantize[=block_size]] [--language=xx] [--translate] [--temperature=t] [--no-context] [--beam=k]",
+            args[0]
+        );
         process::exit(1);
     }
 
@@ -110,7 +172,7 @@ fn main() {
         }
     };
 
-    let whisper_config = match WhisperConfig::load(&format!("{}.cfg", model_name)) {
+    let mut whisper_config = match WhisperConfig::load(&format!("{}.cfg", model_name)) {
         Ok(config) => config,
         Err(e) => {
             eprintln!("Failed to load whisper config: {}", e);
@@ -118,6 +180,15 @@ fn main() {
         }
     };
 
+    if let Some(block_size) = quantize_block_size {
+        let quantization = QuantizationConfig::new().with_block_size(block_size);
+        if let Err(e) = quantization.validate() {
+            eprintln!("Invalid --quantize value: {}", e);
+            process::exit(1);
+        }
+        whisper_config.quantization = Some(quantization);
+    }
+
     println!("Loading model...");
     let whisper: Whisper<Backend> = match load_whisper_model_file(&whisper_config, model_name) {
         Ok(whisper_model) => whisper_model,
@@ -129,15 +200,28 @@ fn main() {
     
     let whisper = whisper.to_device(&device);
 
-    let (text, tokens) = match waveform_to_text(&whisper, &bpe, waveform, sample_rate) {
-        Ok( (text, tokens) ) => (text, tokens), 
-        Err(e) => {
-            eprintln!("Error during transcription: {}", e);
-            process::exit(1);
-        }
+    let decode_options = DecodeOptions {
+        language,
+        task: if translate { Task::Translate } else { Task::Transcribe },
+        temperature,
+        no_context,
+        beam_size,
     };
 
-    fs::write(text_file, text).unwrap_or_else(|e| {
+    let (text, _tokens, segments, language) =
+        match waveform_to_text(&whisper, &bpe, waveform, sample_rate, &decode_options) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("Error during transcription: {}", e);
+                process::exit(1);
+            }
+        };
+    println!("Detected language: {}", language);
+
+    let format = SubtitleFormat::from_extension(text_file);
+    let output = format_transcription(format, &text, &segments);
+
+    fs::write(text_file, output).unwrap_or_else(|e| {
         eprintln!("Error writing transcription file: {}", e);
         process::exit(1);
     });
@@ -1,5 +1,7 @@
 pub mod audio;
 pub mod helper;
 pub mod model;
+pub mod quantize;
+pub mod subtitle;
 pub mod token;
 pub mod transcribe;
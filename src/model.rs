@@ -9,10 +9,17 @@ use burn::{
     tensor::{activation::gelu, backend::Backend, Int, Tensor},
 };
 
+use crate::quantize::{MaybeQuantizedLinear, QuantizationConfig};
+
 #[derive(Config)]
 pub struct WhisperConfig {
     pub audio_encoder_config: AudioEncoderConfig,
     pub text_decoder_config: TextDecoderConfig,
+    /// When set, MLP weight matrices are converted to packed block
+    /// quantization right after loading. Defaults to `None` so configs
+    /// serialized before this field existed keep loading.
+    #[config(default = None)]
+    pub quantization: Option<QuantizationConfig>,
 }
 
 impl WhisperConfig {
@@ -65,6 +72,13 @@ impl<B: Backend> AudioEncoder<B> {
 
         self.ln_post.forward(x)
     }
+
+    fn quantize(self, config: &QuantizationConfig) -> Self {
+        Self {
+            blocks: self.blocks.into_iter().map(|block| block.quantize(config)).collect(),
+            ..self
+        }
+    }
 }
 
 #[derive(Config)]
@@ -113,6 +127,13 @@ impl<B: Backend> TextDecoder<B> {
         // token embedding matrix.
         x.matmul(self.token_embedding.weight.val().transpose())
     }
+
+    fn quantize(self, config: &QuantizationConfig) -> Self {
+        Self {
+            blocks: self.blocks.into_iter().map(|block| block.quantize(config)).collect(),
+            ..self
+        }
+    }
 }
 
 #[derive(Config)]
@@ -137,8 +158,8 @@ impl ResidualAttentionBlockConfig {
             } else {
                 None
             },
-            mlp_fc1: LinearConfig::new(self.n_state, self.n_state * 4).init(),
-            mlp_fc2: LinearConfig::new(self.n_state * 4, self.n_state).init(),
+            mlp_fc1: MaybeQuantizedLinear::new(LinearConfig::new(self.n_state, self.n_state * 4).init()),
+            mlp_fc2: MaybeQuantizedLinear::new(LinearConfig::new(self.n_state * 4, self.n_state).init()),
             mlp_ln: LayerNormConfig::new(self.n_state).init(),
         }
     }
@@ -150,8 +171,8 @@ struct ResidualAttentionBlock<B: Backend> {
     attn_ln: LayerNorm<B>,
     cross_attn: Option<MultiHeadSelfAttention<B>>,
     cross_attn_ln: Option<LayerNorm<B>>,
-    mlp_fc1: Linear<B>,
-    mlp_fc2: Linear<B>,
+    mlp_fc1: MaybeQuantizedLinear<B>,
+    mlp_fc2: MaybeQuantizedLinear<B>,
     mlp_ln: LayerNorm<B>,
 }
 
@@ -168,6 +189,16 @@ impl<B: Backend> ResidualAttentionBlock<B> {
         let mlp_in = self.mlp_ln.forward(x.clone());
         x + self.mlp_fc2.forward(gelu(self.mlp_fc1.forward(mlp_in)))
     }
+
+    /// Converts this block's MLP weight matrices to packed block
+    /// quantization, leaving attention weights, norms, and biases untouched.
+    fn quantize(self, config: &QuantizationConfig) -> Self {
+        Self {
+            mlp_fc1: self.mlp_fc1.quantize(config),
+            mlp_fc2: self.mlp_fc2.quantize(config),
+            ..self
+        }
+    }
 }
 
 #[derive(Config)]
@@ -241,4 +272,14 @@ impl<B: Backend> Whisper<B> {
     pub fn forward_decoder(&self, tokens: Tensor<B, 2, Int>, encoder_output: Tensor<B, 3>) -> Tensor<B, 3> {
         self.decoder.forward(tokens, encoder_output)
     }
+
+    /// Converts every MLP weight matrix to packed block-quantized storage,
+    /// leaving attention weights, norms, embeddings, and biases in full
+    /// precision.
+    pub fn quantize(self, config: &QuantizationConfig) -> Self {
+        Self {
+            encoder: self.encoder.quantize(config),
+            decoder: self.decoder.quantize(config),
+        }
+    }
 }
@@ -0,0 +1,140 @@
+use crate::transcribe::Segment;
+
+/// Supported subtitle output formats, selected from a transcription file's
+/// extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubtitleFormat {
+    PlainText,
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn from_extension(filename: &str) -> Self {
+        match filename.rsplit('.').next() {
+            Some("srt") => SubtitleFormat::Srt,
+            Some("vtt") => SubtitleFormat::Vtt,
+            _ => SubtitleFormat::PlainText,
+        }
+    }
+}
+
+/// Renders `segments` as the given subtitle format, falling back to `text`
+/// verbatim for `SubtitleFormat::PlainText`.
+pub fn format_transcription(format: SubtitleFormat, text: &str, segments: &[Segment]) -> String {
+    match format {
+        SubtitleFormat::PlainText => text.to_string(),
+        SubtitleFormat::Srt => format_srt(segments),
+        SubtitleFormat::Vtt => format_vtt(segments),
+    }
+}
+
+fn format_srt(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, seg)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                format_timestamp_srt(seg.start),
+                format_timestamp_srt(seg.end),
+                seg.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn format_vtt(segments: &[Segment]) -> String {
+    let cues = segments
+        .iter()
+        .map(|seg| {
+            format!(
+                "{} --> {}\n{}\n",
+                format_timestamp_vtt(seg.start),
+                format_timestamp_vtt(seg.end),
+                seg.text
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    format!("WEBVTT\n\n{}", cues)
+}
+
+fn format_timestamp_srt(secs: f64) -> String {
+    let (h, m, s, ms) = split_timestamp(secs);
+    format!("{:02}:{:02}:{:02},{:03}", h, m, s, ms)
+}
+
+fn format_timestamp_vtt(secs: f64) -> String {
+    let (h, m, s, ms) = split_timestamp(secs);
+    format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms)
+}
+
+fn split_timestamp(secs: f64) -> (u64, u64, u64, u64) {
+    let total_ms = (secs * 1000.0).round() as u64;
+    let ms = total_ms % 1000;
+    let total_secs = total_ms / 1000;
+    let s = total_secs % 60;
+    let total_mins = total_secs / 60;
+    let m = total_mins % 60;
+    let h = total_mins / 60;
+    (h, m, s, ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_timestamp_handles_zero() {
+        assert_eq!(split_timestamp(0.0), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn split_timestamp_rounds_fractional_milliseconds() {
+        // 1.2345s -> 1234.5ms, rounds to 1235ms = 1s 235ms.
+        assert_eq!(split_timestamp(1.2345), (0, 0, 1, 235));
+    }
+
+    #[test]
+    fn split_timestamp_carries_across_hours_minutes_seconds() {
+        // 1h 1m 1.001s
+        assert_eq!(split_timestamp(3661.001), (1, 1, 1, 1));
+    }
+
+    #[test]
+    fn format_srt_numbers_cues_from_one() {
+        let segments = vec![
+            Segment { start: 0.0, end: 1.0, text: "hello".into() },
+            Segment { start: 1.0, end: 2.5, text: "world".into() },
+        ];
+        let srt = format_srt(&segments);
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:01,000\nhello\n\n2\n00:00:01,000 --> 00:00:02,500\nworld\n"
+        );
+    }
+
+    #[test]
+    fn format_srt_handles_no_segments() {
+        assert_eq!(format_srt(&[]), "");
+    }
+
+    #[test]
+    fn format_vtt_starts_with_webvtt_header() {
+        let segments = vec![Segment { start: 0.0, end: 1.5, text: "hi".into() }];
+        let vtt = format_vtt(&segments);
+        assert_eq!(vtt, "WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nhi\n");
+    }
+
+    #[test]
+    fn from_extension_recognizes_known_suffixes() {
+        assert_eq!(SubtitleFormat::from_extension("out.srt"), SubtitleFormat::Srt);
+        assert_eq!(SubtitleFormat::from_extension("out.vtt"), SubtitleFormat::Vtt);
+        assert_eq!(SubtitleFormat::from_extension("out.txt"), SubtitleFormat::PlainText);
+        assert_eq!(SubtitleFormat::from_extension("out"), SubtitleFormat::PlainText);
+    }
+}
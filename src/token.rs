@@ -1,7 +1,8 @@
 use tokenizers::Tokenizer;
 
 /// Whisper's special tokens occupy a fixed block at the end of the GPT-2 BPE
-/// vocabulary.
+/// vocabulary. `Timestamp` addresses the whole contiguous sub-block: each
+/// timestamp token encodes a 0.02s increment starting at `<|0.00|>`.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SpecialToken {
     EndOfText,
@@ -11,19 +12,37 @@ pub enum SpecialToken {
     StartOfPrev,
     NoSpeech,
     NoTimestamps,
+    Timestamp(f64),
     Language(&'static str),
 }
 
+/// The language codes Whisper was trained to recognize, in the same order
+/// as their `<|lang|>` special tokens.
+pub const LANGUAGE_CODES: &[&str] = &[
+    "en", "zh", "de", "es", "ru", "ko", "fr", "ja", "pt", "tr", "pl", "ca", "nl", "ar", "sv", "it",
+    "id", "hi", "fi", "vi", "he", "uk", "el", "ms", "cs", "ro", "da", "hu", "ta", "no", "th", "ur",
+    "hr", "bg", "lt", "la", "mi", "ml", "cy", "sk", "te", "fa", "lv", "bn", "sr", "az", "sl", "kn",
+    "et", "mk", "br", "eu", "is", "hy", "ne", "mn", "bs", "kk", "sq", "sw", "gl", "mr", "pa", "si",
+    "km", "sn", "yo", "so", "af", "oc", "ka", "be", "tg", "sd", "gu", "am", "yi", "lo", "uz", "fo",
+    "ht", "ps", "tk", "nn", "mt", "sa", "lb", "my", "bo", "tl", "mg", "as", "tt", "haw", "ln", "ha",
+    "ba", "jw", "su",
+];
+
 pub struct Gpt2Tokenizer {
     tokenizer: Tokenizer,
+    timestamp_begin_id: usize,
 }
 
 impl Gpt2Tokenizer {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
         let bytes = include_bytes!("../tokenizer.json");
         let tokenizer = Tokenizer::from_bytes(bytes.as_slice())?;
+        let timestamp_begin_id = tokenizer
+            .token_to_id("<|notimestamps|>")
+            .map(|id| id as usize + 1)
+            .ok_or("tokenizer is missing the <|notimestamps|> special token")?;
 
-        Ok(Self { tokenizer })
+        Ok(Self { tokenizer, timestamp_begin_id })
     }
 
     pub fn encode(&self, text: &str, special: bool) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
@@ -32,11 +51,18 @@ impl Gpt2Tokenizer {
     }
 
     pub fn decode(&self, tokens: &[usize], skip_special: bool) -> Result<String, Box<dyn std::error::Error>> {
-        let ids: Vec<u32> = tokens.iter().map(|&t| t as u32).collect();
+        let ids: Vec<u32> = tokens
+            .iter()
+            .filter(|&&t| !skip_special || t < self.timestamp_begin_id)
+            .map(|&t| t as u32)
+            .collect();
         Ok(self.tokenizer.decode(&ids, skip_special)?)
     }
 
-    /// Resolves a [`SpecialToken`] to its vocabulary id.
+    /// Resolves a [`SpecialToken`] to its vocabulary id. For
+    /// `SpecialToken::Timestamp(secs)`, returns the id of the timestamp token
+    /// `secs` seconds after `<|0.00|>`; passing `0.0` returns the id of the
+    /// first timestamp token, i.e. `timestamp_begin`.
     pub fn special_token(&self, token: SpecialToken) -> Option<usize> {
         let name = match token {
             SpecialToken::EndOfText => "<|endoftext|>".to_string(),
@@ -47,8 +73,27 @@ impl Gpt2Tokenizer {
             SpecialToken::NoSpeech => "<|nospeech|>".to_string(),
             SpecialToken::NoTimestamps => "<|notimestamps|>".to_string(),
             SpecialToken::Language(code) => format!("<|{}|>", code),
+            SpecialToken::Timestamp(secs) => {
+                let offset = (secs / 0.02).round() as usize;
+                return Some(self.timestamp_begin_id + offset);
+            }
         };
 
         self.tokenizer.token_to_id(&name).map(|id| id as usize)
     }
+
+    /// Returns every `(language code, token id)` pair in the language token
+    /// block, for restricting a decoder step's argmax to language detection.
+    pub fn language_tokens(&self) -> Vec<(&'static str, usize)> {
+        LANGUAGE_CODES
+            .iter()
+            .filter_map(|&code| self.special_token(SpecialToken::Language(code)).map(|id| (code, id)))
+            .collect()
+    }
+
+    /// Looks up a `<|lang|>` token id from an arbitrary (possibly
+    /// user-supplied) language code.
+    pub fn language_token_id(&self, code: &str) -> Option<usize> {
+        self.tokenizer.token_to_id(&format!("<|{}|>", code)).map(|id| id as usize)
+    }
 }
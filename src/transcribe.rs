@@ -5,8 +5,53 @@ use crate::model::Whisper;
 use crate::token::{Gpt2Tokenizer, SpecialToken};
 
 const SECONDS_PER_WINDOW: f64 = 30.0;
+const SECONDS_PER_TIMESTAMP: f64 = 0.02;
 const MAX_DECODE_TOKENS: usize = 448;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Whether the model should transcribe in the source language or translate
+/// into English.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Task {
+    Transcribe,
+    Translate,
+}
+
+/// Controls how [`waveform_to_text`] decodes.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    /// Forces the source language instead of auto-detecting it.
+    pub language: Option<String>,
+    pub task: Task,
+    /// `0.0` decodes greedily; anything higher samples from the softened
+    /// softmax distribution.
+    pub temperature: f32,
+    /// When `true`, each window is decoded from scratch instead of carrying
+    /// the previous window's trailing tokens in as `<|startofprev|>` context.
+    pub no_context: bool,
+    /// `None` or `Some(1)` decodes greedily token-by-token; `Some(k > 1)`
+    /// keeps the `k` highest scoring hypotheses at each step.
+    pub beam_size: Option<usize>,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            language: None,
+            task: Task::Transcribe,
+            temperature: 0.0,
+            no_context: false,
+            beam_size: None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum WhisperError {
     Forward(String),
@@ -22,59 +67,370 @@ impl std::fmt::Display for WhisperError {
     }
 }
 
+/// Picks the most probable `<|lang|>` token after a single decoder step on
+/// the first 30s window, returning its language code (e.g. `"en"`).
+pub fn detect_language<B: Backend>(
+    whisper: &Whisper<B>,
+    bpe: &Gpt2Tokenizer,
+    waveform: &[f32],
+    sample_rate: usize,
+) -> Result<String, WhisperError> {
+    let window_len = (SECONDS_PER_WINDOW * sample_rate as f64) as usize;
+    let window = &waveform[..waveform.len().min(window_len)];
+    let mel: Tensor<B, 3> = prep_audio(window, sample_rate as f64);
+
+    let encoder_output = whisper.forward_encoder(mel);
+
+    let sot = bpe.special_token(SpecialToken::StartOfTranscript).unwrap();
+    let device: B::Device = Default::default();
+    let tokens: Tensor<B, 2, Int> = Tensor::from_data(Data::new(vec![sot as i64], [1, 1].into()), &device);
+
+    let logits = whisper.forward_decoder(tokens, encoder_output);
+    let last_logits: Vec<f32> = logits
+        .slice([0..1, 0..1])
+        .flatten::<1>(0, 2)
+        .into_data()
+        .convert::<f32>()
+        .value;
+
+    let language_tokens = bpe.language_tokens();
+    let (code, _) = language_tokens
+        .iter()
+        .max_by(|(_, a), (_, b)| last_logits[**a].partial_cmp(&last_logits[**b]).unwrap())
+        .ok_or_else(|| WhisperError::Tokenizer("tokenizer has no language tokens".into()))?;
+
+    Ok(code.to_string())
+}
+
 /// Runs the encoder/decoder loop over `waveform`, 30 second window at a
-/// time, greedily decoding until `<|endoftext|>` or the window fills up.
-/// Returns the concatenated plain text and the raw token ids.
+/// time, decoding per `options` until `<|endoftext|>` or the window fills up.
+/// Returns the concatenated plain text, the raw token ids, the recovered
+/// segments, and the language used.
 pub fn waveform_to_text<B: Backend>(
     whisper: &Whisper<B>,
     bpe: &Gpt2Tokenizer,
     waveform: Vec<f32>,
     sample_rate: usize,
-) -> Result<(String, Vec<usize>), WhisperError> {
+    options: &DecodeOptions,
+) -> Result<(String, Vec<usize>, Vec<Segment>, String), WhisperError> {
     let samples_per_window = (SECONDS_PER_WINDOW * sample_rate as f64) as usize;
 
+    let timestamp_begin_id = bpe
+        .special_token(SpecialToken::Timestamp(0.0))
+        .ok_or_else(|| WhisperError::Tokenizer("missing timestamp token block".into()))?;
+
+    let language = match &options.language {
+        Some(language) => language.clone(),
+        None => detect_language(whisper, bpe, &waveform, sample_rate)?,
+    };
+
+    let mut prompt = vec![bpe.special_token(SpecialToken::StartOfTranscript).unwrap()];
+    prompt.push(
+        bpe.language_token_id(&language)
+            .ok_or_else(|| WhisperError::Tokenizer(format!("unknown language code {}", language)))?,
+    );
+    prompt.push(match options.task {
+        Task::Transcribe => bpe.special_token(SpecialToken::Transcribe).unwrap(),
+        Task::Translate => bpe.special_token(SpecialToken::Translate).unwrap(),
+    });
+
     let mut all_tokens = Vec::new();
+    let mut segments = Vec::new();
+    let mut window_offset_secs = 0.0;
     let mut consumed = 0;
+    let mut prev_window_tail: Vec<usize> = Vec::new();
 
     while consumed < waveform.len() {
         let window_end = (consumed + samples_per_window).min(waveform.len());
         let window = &waveform[consumed..window_end];
         let mel = prep_audio(window, sample_rate as f64);
 
-        let tokens = decode_window::<B>(whisper, bpe, &mel)?;
-        all_tokens.extend(tokens);
+        let mut initial_tokens = Vec::new();
+        if !options.no_context && !prev_window_tail.is_empty() {
+            initial_tokens.push(bpe.special_token(SpecialToken::StartOfPrev).unwrap());
+            initial_tokens.extend(&prev_window_tail);
+        }
+        initial_tokens.extend(&prompt);
+
+        let (tokens, window_segments) = decode_window::<B>(
+            whisper,
+            bpe,
+            &mel,
+            timestamp_begin_id,
+            window_offset_secs,
+            &initial_tokens,
+            options,
+        )?;
+
+        let (generated, tail) = accumulate_window(&tokens, initial_tokens.len());
+        prev_window_tail = tail;
+        all_tokens.extend_from_slice(generated);
+        segments.extend(window_segments);
 
         consumed = window_end;
+        window_offset_secs += SECONDS_PER_WINDOW;
     }
 
     let text = bpe
         .decode(&all_tokens, true)
         .map_err(|e| WhisperError::Tokenizer(e.to_string()))?;
 
-    Ok((text, all_tokens))
+    Ok((text, all_tokens, segments, language))
 }
 
-/// Greedily decodes a single (<=30s) mel window.
+/// Decodes a single (<=30s) mel window starting from `initial_tokens`,
+/// greedily/with temperature for `options.beam_size` of `None`/`Some(1)`,
+/// or via beam search otherwise.
 fn decode_window<B: Backend>(
     whisper: &Whisper<B>,
     bpe: &Gpt2Tokenizer,
     mel: &Tensor<B, 3>,
-) -> Result<Vec<usize>, WhisperError> {
-    let device: B::Device = Default::default();
+    timestamp_begin_id: usize,
+    window_offset_secs: f64,
+    initial_tokens: &[usize],
+    options: &DecodeOptions,
+) -> Result<(Vec<usize>, Vec<Segment>), WhisperError> {
     let encoder_output = whisper.forward_encoder(mel.clone());
-
-    let mut tokens = vec![bpe.special_token(SpecialToken::StartOfTranscript).unwrap()];
     let eot = bpe.special_token(SpecialToken::EndOfText).unwrap();
 
-    for _ in 0..MAX_DECODE_TOKENS {
-        let token_tensor: Tensor<B, 2, Int> = Tensor::from_data(
-            Data::new(tokens.iter().map(|&t| t as i64).collect(), [1, tokens.len()].into()),
-            &device,
+    let tokens = match options.beam_size {
+        Some(beam_width) if beam_width > 1 => {
+            beam_search(whisper, &encoder_output, initial_tokens, eot, beam_width)
+        }
+        _ => greedy_decode(whisper, &encoder_output, initial_tokens, eot, options.temperature),
+    };
+
+    let generated = generated_suffix(&tokens, initial_tokens.len());
+
+    let segments = tokens_to_segments(generated, timestamp_begin_id, window_offset_secs, |text_tokens| {
+        bpe.decode(text_tokens, true).map_err(|e| WhisperError::Tokenizer(e.to_string()))
+    })?;
+
+    Ok((tokens, segments))
+}
+
+/// Slices off the `initial_tokens` prefix `decode_window` was seeded with,
+/// leaving only the tokens actually generated in this window.
+fn generated_suffix(tokens: &[usize], initial_tokens_len: usize) -> &[usize] {
+    &tokens[initial_tokens_len.min(tokens.len())..]
+}
+
+/// The generated suffix of one window's `decode_window` return, plus its
+/// trailing (up to 64-token) slice to carry as the next window's context.
+fn accumulate_window(tokens: &[usize], initial_tokens_len: usize) -> (&[usize], Vec<usize>) {
+    let generated = generated_suffix(tokens, initial_tokens_len);
+    let tail = generated.iter().rev().take(64).rev().copied().collect();
+    (generated, tail)
+}
+
+/// Splits `tokens` into `(start, end, text)` segments wherever a pair of
+/// timestamp tokens brackets a span of text; an unmatched trailing
+/// timestamp or an empty/whitespace-only span is dropped. `decode` is
+/// injected so this stays pure and unit-testable without a tokenizer.
+fn tokens_to_segments(
+    tokens: &[usize],
+    timestamp_begin_id: usize,
+    window_offset_secs: f64,
+    decode: impl Fn(&[usize]) -> Result<String, WhisperError>,
+) -> Result<Vec<Segment>, WhisperError> {
+    let mut segments = Vec::new();
+    let mut pending_start: Option<f64> = None;
+    let mut pending_text_tokens: Vec<usize> = Vec::new();
+
+    for &token in tokens {
+        if token >= timestamp_begin_id {
+            let time = (token - timestamp_begin_id) as f64 * SECONDS_PER_TIMESTAMP + window_offset_secs;
+
+            match pending_start {
+                Some(start) => {
+                    let text = decode(&pending_text_tokens)?;
+                    if !text.trim().is_empty() {
+                        segments.push(Segment { start, end: time, text: text.trim().to_string() });
+                    }
+                    pending_start = None;
+                    pending_text_tokens.clear();
+                }
+                None => pending_start = Some(time),
+            }
+        } else {
+            pending_text_tokens.push(token);
+        }
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tokens_to_segments_tests {
+    use super::*;
+
+    const BEGIN: usize = 100;
+
+    fn decode_as_digits(tokens: &[usize]) -> Result<String, WhisperError> {
+        Ok(tokens.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" "))
+    }
+
+    #[test]
+    fn pairs_bracketing_timestamps_into_a_segment() {
+        let tokens = [BEGIN, 1, 2, BEGIN + 50];
+        let segments = tokens_to_segments(&tokens, BEGIN, 0.0, decode_as_digits).unwrap();
+        assert_eq!(segments, vec![Segment { start: 0.0, end: 1.0, text: "1 2".to_string() }]);
+    }
+
+    #[test]
+    fn applies_the_window_offset_to_both_ends() {
+        let tokens = [BEGIN, 1, BEGIN + 10];
+        let segments = tokens_to_segments(&tokens, BEGIN, 30.0, decode_as_digits).unwrap();
+        assert_eq!(segments, vec![Segment { start: 30.0, end: 30.2, text: "1".to_string() }]);
+    }
+
+    #[test]
+    fn drops_an_unmatched_trailing_timestamp() {
+        let tokens = [BEGIN, 1, 2, BEGIN + 5, 3];
+        let segments = tokens_to_segments(&tokens, BEGIN, 0.0, decode_as_digits).unwrap();
+        assert_eq!(segments, vec![Segment { start: 0.0, end: 0.1, text: "1 2".to_string() }]);
+    }
+
+    #[test]
+    fn back_to_back_timestamp_pairs_each_start_their_own_segment() {
+        let tokens = [BEGIN, 1, BEGIN + 10, BEGIN + 10, 2, BEGIN + 20];
+        let segments = tokens_to_segments(&tokens, BEGIN, 0.0, decode_as_digits).unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment { start: 0.0, end: 0.2, text: "1".to_string() },
+                Segment { start: 0.2, end: 0.4, text: "2".to_string() },
+            ]
         );
+    }
 
-        let logits = whisper.forward_decoder(token_tensor, encoder_output.clone());
-        let last_logits = logits.slice([0..1, tokens.len() - 1..tokens.len()]).flatten::<1>(0, 2);
-        let next_token = last_logits.argmax(0).into_scalar().elem::<i64>() as usize;
+    #[test]
+    fn drops_a_span_whose_decoded_text_is_whitespace_only() {
+        let tokens = [BEGIN, 1, BEGIN + 5];
+        let segments = tokens_to_segments(&tokens, BEGIN, 0.0, |_| Ok("   ".to_string())).unwrap();
+        assert!(segments.is_empty());
+    }
+
+    #[test]
+    fn drops_a_span_with_no_text_tokens_between_timestamps() {
+        let tokens = [BEGIN, BEGIN + 5];
+        let segments = tokens_to_segments(&tokens, BEGIN, 0.0, decode_as_digits).unwrap();
+        assert!(segments.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod multi_window_accumulation_tests {
+    use super::*;
+
+    #[test]
+    fn generated_suffix_excludes_initial_tokens_even_when_fewer_than_64_were_generated() {
+        // Regression for 260e72c: a window that generates only a few new
+        // tokens must not have its initial_tokens prompt/control prefix
+        // leak into what's considered "generated".
+        let initial_tokens = vec![1, 2, 3, 4, 5];
+        let mut tokens = initial_tokens.clone();
+        tokens.extend([10, 11, 12]);
+
+        assert_eq!(generated_suffix(&tokens, initial_tokens.len()), &[10, 11, 12]);
+    }
+
+    #[test]
+    fn generated_suffix_is_empty_when_decode_window_produced_nothing_new() {
+        let initial_tokens = vec![1, 2, 3];
+        assert_eq!(generated_suffix(&initial_tokens, initial_tokens.len()), &[] as &[usize]);
+    }
+
+    #[test]
+    fn accumulating_across_two_context_carrying_windows_does_not_duplicate_text() {
+        // Regression for 157b1cf/260e72c: with context carry on, window 2's
+        // initial_tokens include window 1's carried tail plus the repeated
+        // prompt. Accumulating both windows' generated tokens (not their
+        // full decode_window return) into `all_tokens` must not re-emit
+        // window 1's own text a second time.
+        let initial1 = vec![1, 2, 3]; // <|startoftranscript|>, lang, task
+        let mut tokens1 = initial1.clone();
+        tokens1.extend([10, 11]);
+        let (generated1, tail1) = accumulate_window(&tokens1, initial1.len());
+
+        let mut all_tokens = Vec::new();
+        all_tokens.extend_from_slice(generated1);
+
+        let mut initial2 = vec![99]; // <|startofprev|>
+        initial2.extend(&tail1);
+        initial2.extend(&initial1);
+        let mut tokens2 = initial2.clone();
+        tokens2.extend([20, 21]);
+        let (generated2, _tail2) = accumulate_window(&tokens2, initial2.len());
+        all_tokens.extend_from_slice(generated2);
+
+        assert_eq!(all_tokens, vec![10, 11, 20, 21]);
+    }
+
+    #[test]
+    fn decode_window_only_builds_segments_from_tokens_generated_in_this_window() {
+        // Regression for 3fe2f0a: a carried-over <|startofprev|> tail that
+        // happens to contain a real timestamp token must not be reinterpreted
+        // against the current window's offset when building segments.
+        const BEGIN: usize = 100;
+        let initial_tokens = vec![99, BEGIN + 5, 1, 2, 3]; // <|startofprev|>, stray timestamp, prompt
+        let mut tokens = initial_tokens.clone();
+        tokens.extend([BEGIN, 7, BEGIN + 10]);
+
+        let generated = generated_suffix(&tokens, initial_tokens.len());
+        let segments = tokens_to_segments(generated, BEGIN, 0.0, |t| {
+            Ok(t.iter().map(|t| t.to_string()).collect::<Vec<_>>().join(" "))
+        })
+        .unwrap();
+
+        assert_eq!(segments, vec![Segment { start: 0.0, end: 0.2, text: "7".to_string() }]);
+    }
+}
+
+fn decode_step<B: Backend>(
+    whisper: &Whisper<B>,
+    encoder_output: &Tensor<B, 3>,
+    tokens: &[usize],
+) -> Vec<f32> {
+    let device: B::Device = Default::default();
+    let token_tensor: Tensor<B, 2, Int> = Tensor::from_data(
+        Data::new(tokens.iter().map(|&t| t as i64).collect(), [1, tokens.len()].into()),
+        &device,
+    );
+
+    let logits = whisper.forward_decoder(token_tensor, encoder_output.clone());
+    logits
+        .slice([0..1, tokens.len() - 1..tokens.len()])
+        .flatten::<1>(0, 2)
+        .into_data()
+        .convert::<f32>()
+        .value
+}
+
+/// Greedily decodes, or samples from the temperature-softened distribution
+/// when `temperature > 0.0`.
+fn greedy_decode<B: Backend>(
+    whisper: &Whisper<B>,
+    encoder_output: &Tensor<B, 3>,
+    initial_tokens: &[usize],
+    eot: usize,
+    temperature: f32,
+) -> Vec<usize> {
+    let mut tokens = initial_tokens.to_vec();
+
+    for _ in 0..MAX_DECODE_TOKENS {
+        let logits = decode_step(whisper, encoder_output, &tokens);
+
+        let next_token = if temperature > 0.0 {
+            sample_with_temperature(&logits, temperature)
+        } else {
+            logits
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .map(|(idx, _)| idx)
+                .unwrap()
+        };
 
         if next_token == eot {
             break;
@@ -82,5 +438,114 @@ fn decode_window<B: Backend>(
         tokens.push(next_token);
     }
 
-    Ok(tokens)
+    tokens
+}
+
+/// Samples an index from `logits` after dividing by `temperature` and
+/// applying softmax, using a simple xorshift PRNG seeded from the logits
+/// themselves (no external RNG dependency needed for this one draw).
+fn sample_with_temperature(logits: &[f32], temperature: f32) -> usize {
+    let max_logit = logits.iter().cloned().fold(f32::MIN, f32::max);
+    let weights: Vec<f32> = logits
+        .iter()
+        .map(|&l| ((l - max_logit) / temperature).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+
+    let mut seed = logits.iter().fold(0x9e3779b9u32, |acc, &l| acc ^ l.to_bits().rotate_left(5));
+    seed ^= seed << 13;
+    seed ^= seed >> 17;
+    seed ^= seed << 5;
+    let draw = (seed as f32 / u32::MAX as f32) * total;
+
+    let mut cumulative = 0.0;
+    for (idx, &w) in weights.iter().enumerate() {
+        cumulative += w;
+        if draw <= cumulative {
+            return idx;
+        }
+    }
+    weights.len() - 1
+}
+
+#[derive(Clone)]
+struct BeamHypothesis {
+    tokens: Vec<usize>,
+    log_prob: f32,
+    finished: bool,
+}
+
+impl BeamHypothesis {
+    /// Length-normalized log-prob, so a beam that reaches `eot` early isn't
+    /// compared on raw cumulative log-prob against a longer, still-live one.
+    fn score(&self) -> f32 {
+        self.log_prob / self.tokens.len() as f32
+    }
+}
+
+/// Simple beam search: at each step, every live hypothesis expands by its
+/// top candidates and only the `beam_width` highest cumulative log-prob
+/// hypotheses survive.
+fn beam_search<B: Backend>(
+    whisper: &Whisper<B>,
+    encoder_output: &Tensor<B, 3>,
+    initial_tokens: &[usize],
+    eot: usize,
+    beam_width: usize,
+) -> Vec<usize> {
+    let mut beams = vec![BeamHypothesis {
+        tokens: initial_tokens.to_vec(),
+        log_prob: 0.0,
+        finished: false,
+    }];
+
+    for _ in 0..MAX_DECODE_TOKENS {
+        if beams.iter().all(|b| b.finished) {
+            break;
+        }
+
+        let mut candidates = Vec::new();
+        for beam in &beams {
+            if beam.finished {
+                candidates.push(beam.clone());
+                continue;
+            }
+
+            let logits = decode_step(whisper, encoder_output, &beam.tokens);
+            let log_probs = log_softmax(&logits);
+
+            let mut scored: Vec<(usize, f32)> = log_probs.into_iter().enumerate().collect();
+            scored.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+
+            for &(token, log_prob) in scored.iter().take(beam_width) {
+                let mut tokens = beam.tokens.clone();
+                let finished = token == eot;
+                if !finished {
+                    tokens.push(token);
+                }
+                candidates.push(BeamHypothesis {
+                    tokens,
+                    log_prob: beam.log_prob + log_prob,
+                    finished,
+                });
+            }
+        }
+
+        candidates.sort_by(|a, b| b.score().partial_cmp(&a.score()).unwrap());
+        candidates.truncate(beam_width);
+        beams = candidates;
+    }
+
+    beams
+        .into_iter()
+        .max_by(|a, b| a.score().partial_cmp(&b.score()).unwrap())
+        .map(|b| b.tokens)
+        .unwrap_or_default()
+}
+
+fn log_softmax(logits: &[f32]) -> Vec<f32> {
+    let max_logit = logits.iter().cloned().fold(f32::MIN, f32::max);
+    let exp_sum: f32 = logits.iter().map(|&l| (l - max_logit).exp()).sum();
+    let log_sum = exp_sum.ln();
+    logits.iter().map(|&l| l - max_logit - log_sum).collect()
 }